@@ -0,0 +1,37 @@
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::message::proto::ServerError;
+
+/// Error encountered while holding or establishing a connection to a broker
+#[derive(Debug, Error)]
+pub enum ConnectionError {
+    /// the connection was closed, either by the broker or because of a network error
+    #[error("the connection was closed")]
+    Disconnected,
+}
+
+/// Error returned by `ServiceDiscovery` while resolving a topic to a broker
+#[derive(Debug, Error)]
+pub enum ServiceDiscoveryError {
+    /// error on the underlying connection used to perform the lookup
+    #[error("connection error: {0}")]
+    Connection(#[from] ConnectionError),
+    /// the broker has no known owner for the requested topic
+    #[error("no broker found for the topic")]
+    NotFound,
+    /// the broker answered the lookup query with an error
+    #[error("query error: {0:?}, message: {1:?}")]
+    Query(Option<ServerError>, Option<String>),
+    /// the lookup's retry/redirect loop ran longer than the configured `lookup_timeout`
+    #[error("lookup timed out after {0:?}")]
+    Timeout(Duration),
+    /// the lookup followed more redirects than `max_redirects` allows, or detected a cycle
+    #[error("lookup exceeded the maximum number of redirects, path: {0:?}")]
+    TooManyRedirects(Vec<String>),
+}
+
+/// maps a broker-reported error code to a `ServerError`, if recognized
+pub(crate) fn server_error(code: i32) -> Option<ServerError> {
+    ServerError::from_i32(code)
+}
@@ -5,10 +5,199 @@ use crate::message::proto::{
     command_lookup_topic_response, command_partitioned_topic_metadata_response,
     CommandLookupTopicResponse,
 };
-use futures::{future::try_join_all, FutureExt};
-use std::{sync::Arc, time::Duration};
+use futures::{
+    future::{select, Either},
+    pin_mut,
+    stream::FuturesUnordered,
+    StreamExt,
+};
+use rand::Rng;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Once, RwLock,
+    },
+    time::{Duration, Instant},
+};
 use url::Url;
 
+/// default upper bound on the time spent discovering a topic, covering the
+/// whole redirect/retry loop rather than any single RPC
+const DEFAULT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// default cap on the number of partition lookups driven concurrently by
+/// `lookup_partitioned_topic`
+const DEFAULT_MAX_CONCURRENT_LOOKUPS: usize = 16;
+
+/// default time a resolved `BrokerAddress` is trusted before it must be looked up again
+const DEFAULT_LOOKUP_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// how long before expiry the background task proactively refreshes a cache entry
+const DEFAULT_LOOKUP_CACHE_REFRESH_AHEAD: Duration = Duration::from_secs(60);
+
+/// how often the background task scans the cache for entries nearing expiry
+const LOOKUP_CACHE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// default cap on the number of redirects followed by a single `lookup_topic` call
+const DEFAULT_MAX_REDIRECTS: u32 = 20;
+
+struct CacheEntry {
+    address: BrokerAddress,
+    inserted_at: Instant,
+}
+
+/// caches the `BrokerAddress` a topic resolves to so that producer/consumer
+/// creation and reconnects don't have to re-walk the redirect/proxy lookup
+/// flow for topics whose ownership rarely changes
+///
+/// entries expire after `ttl` and are eagerly refreshed in the background
+/// once they're within `refresh_ahead` of expiring; an entry is also
+/// invalidated when a lookup of its topic (including a background refresh)
+/// is redirected or loses its connection. A cache hit is returned without
+/// contacting any broker, so a change of the topic's owner is not noticed
+/// until one of those happens or the entry expires
+struct LookupCache {
+    entries: RwLock<HashMap<String, CacheEntry>>,
+    // stored as millis rather than `Duration` so `with_lookup_cache_ttl` can
+    // mutate them in place without replacing the `Arc<LookupCache>` identity
+    // that the background refresh task (and every clone of `ServiceDiscovery`)
+    // already holds a reference to
+    ttl_millis: AtomicU64,
+    refresh_ahead_millis: AtomicU64,
+}
+
+impl LookupCache {
+    fn new(ttl: Duration, refresh_ahead: Duration) -> Self {
+        LookupCache {
+            entries: RwLock::new(HashMap::new()),
+            ttl_millis: AtomicU64::new(ttl.as_millis() as u64),
+            refresh_ahead_millis: AtomicU64::new(refresh_ahead.as_millis() as u64),
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_millis.load(Ordering::Relaxed))
+    }
+
+    fn refresh_ahead(&self) -> Duration {
+        Duration::from_millis(self.refresh_ahead_millis.load(Ordering::Relaxed))
+    }
+
+    fn set_ttl(&self, ttl: Duration) {
+        self.ttl_millis
+            .store(ttl.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn get(&self, topic: &str) -> Option<BrokerAddress> {
+        let entries = self.entries.read().unwrap();
+        let entry = entries.get(topic)?;
+        if entry.inserted_at.elapsed() >= self.ttl() {
+            None
+        } else {
+            Some(entry.address.clone())
+        }
+    }
+
+    fn insert(&self, topic: String, address: BrokerAddress) {
+        self.entries.write().unwrap().insert(
+            topic,
+            CacheEntry {
+                address,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    fn invalidate(&self, topic: &str) {
+        self.entries.write().unwrap().remove(topic);
+    }
+
+    /// removes entries that are already past their TTL, which `get` already
+    /// treats as absent; the background task re-resolves every entry nearing
+    /// expiry whether or not its topic is still looked up, so an entry whose
+    /// refreshes keep succeeding is never removed here, and the map holds one
+    /// entry per distinct topic looked up
+    fn evict_expired(&self) {
+        let ttl = self.ttl();
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+    }
+
+    /// topics whose entry is still valid but will expire within `refresh_ahead`
+    fn topics_nearing_expiry(&self) -> Vec<String> {
+        let ttl = self.ttl();
+        let refresh_ahead = self.refresh_ahead();
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(topic, entry)| {
+                let age = entry.inserted_at.elapsed();
+                if age < ttl && age + refresh_ahead >= ttl {
+                    Some(topic.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Configures the backoff used while retrying topic lookups that fail with
+/// `ServiceNotReady`
+///
+/// This mirrors `ConnectionRetryOptions`: the delay between attempts grows
+/// exponentially from `min_backoff` up to `max_backoff`, with a random jitter
+/// added so that many clients retrying at once don't all hammer the broker
+/// in lockstep.
+#[derive(Debug, Clone)]
+pub struct LookupRetryOptions {
+    /// base delay of the first retry, before jitter is added
+    pub min_backoff: Duration,
+    /// cap on the exponential delay between two retries; the jitter is added
+    /// on top of it, so a wait can reach 1.5 times this value
+    pub max_backoff: Duration,
+    /// number of times a `ServiceNotReady` response is retried before giving up
+    pub max_retries: u32,
+}
+
+impl Default for LookupRetryOptions {
+    fn default() -> Self {
+        LookupRetryOptions {
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retries: 20,
+        }
+    }
+}
+
+/// computes a truncated exponential backoff delay with jitter in [0, delay/2]
+fn lookup_retry_delay(options: &LookupRetryOptions, attempt: u32) -> Duration {
+    let exponential = options
+        .min_backoff
+        .saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX));
+    let delay = exponential.min(options.max_backoff);
+    let jitter = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+    delay + Duration::from_millis(jitter)
+}
+
+/// records `hop` as the next broker visited in a redirect chain and reports
+/// whether the chain should be abandoned, either because it ran past
+/// `max_redirects` or because `hop` was already visited (a redirect cycle)
+fn redirect_chain_exhausted(
+    hop: String,
+    redirects: u32,
+    max_redirects: u32,
+    path: &mut Vec<String>,
+    visited: &mut HashSet<String>,
+) -> bool {
+    path.push(hop.clone());
+    redirects > max_redirects || !visited.insert(hop)
+}
+
 /// Look up broker addresses for topics and partitioned topics
 ///
 /// The ServiceDiscovery object provides a single interface to start
@@ -17,11 +206,147 @@ use url::Url;
 #[derive(Clone)]
 pub struct ServiceDiscovery<Exe: Executor + ?Sized> {
     manager: Arc<ConnectionManager<Exe>>,
+    lookup_retry_options: LookupRetryOptions,
+    lookup_timeout: Duration,
+    max_concurrent_lookups: usize,
+    lookup_cache: Arc<LookupCache>,
+    max_redirects: u32,
+    // guards the one-time spawn of the cache refresh task, deferred until the
+    // first actual lookup so it picks up whatever the builder chain ends up
+    // configuring rather than the defaults in place at `with_manager` time
+    refresh_task_init: Arc<Once>,
 }
 
 impl<Exe: Executor> ServiceDiscovery<Exe> {
     pub fn with_manager(manager: Arc<ConnectionManager<Exe>>) -> Self {
-        ServiceDiscovery { manager }
+        ServiceDiscovery {
+            manager,
+            lookup_retry_options: LookupRetryOptions::default(),
+            lookup_timeout: DEFAULT_LOOKUP_TIMEOUT,
+            max_concurrent_lookups: DEFAULT_MAX_CONCURRENT_LOOKUPS,
+            lookup_cache: Arc::new(LookupCache::new(
+                DEFAULT_LOOKUP_CACHE_TTL,
+                DEFAULT_LOOKUP_CACHE_REFRESH_AHEAD,
+            )),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            refresh_task_init: Arc::new(Once::new()),
+        }
+    }
+
+    /// overrides the default lookup retry backoff, typically set from the `Pulsar` builder
+    pub fn with_lookup_retry_options(mut self, lookup_retry_options: LookupRetryOptions) -> Self {
+        self.lookup_retry_options = lookup_retry_options;
+        self
+    }
+
+    /// overrides the default end-to-end lookup timeout, typically set from the `Pulsar` builder
+    pub fn with_lookup_timeout(mut self, lookup_timeout: Duration) -> Self {
+        self.lookup_timeout = lookup_timeout;
+        self
+    }
+
+    /// overrides how many partition lookups `lookup_partitioned_topic` drives at once,
+    /// typically set from the `Pulsar` builder
+    pub fn with_max_concurrent_lookups(mut self, max_concurrent_lookups: usize) -> Self {
+        self.max_concurrent_lookups = max_concurrent_lookups;
+        self
+    }
+
+    /// overrides the default lookup cache TTL, typically set from the `Pulsar` builder
+    ///
+    /// mutates the existing `LookupCache` in place rather than replacing
+    /// `self.lookup_cache` with a new `Arc`, so this can be called even after
+    /// the background refresh task has been spawned against the current cache
+    pub fn with_lookup_cache_ttl(self, ttl: Duration) -> Self {
+        self.lookup_cache.set_ttl(ttl);
+        self
+    }
+
+    /// overrides how many redirects a single `lookup_topic` call will follow before
+    /// giving up, typically set from the `Pulsar` builder
+    pub fn with_max_redirects(mut self, max_redirects: u32) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// spawns the cache refresh task at most once per `ServiceDiscovery` lineage,
+    /// the first time a lookup is actually performed; called from every public
+    /// lookup method instead of from `with_manager`, so the task is spawned
+    /// after the builder chain has finished configuring retry/timeout/redirect
+    /// options rather than capturing whatever defaults were in place earlier
+    fn ensure_cache_refresh_task(&self) {
+        self.refresh_task_init
+            .clone()
+            .call_once(|| self.spawn_cache_refresh_task());
+    }
+
+    /// spawns the background task that proactively refreshes cache entries
+    /// nearing expiry, so hot topics never pay lookup latency on the critical path
+    /// holds only `Weak` references to the manager and cache, so the task
+    /// exits on its own once every other handle to this `ServiceDiscovery`
+    /// (and its connection manager) has been dropped, instead of leaking a
+    /// loop - and the connection manager it keeps alive - for the rest of
+    /// the process
+    fn spawn_cache_refresh_task(&self) {
+        let manager = Arc::downgrade(&self.manager);
+        let lookup_cache = Arc::downgrade(&self.lookup_cache);
+        let lookup_retry_options = self.lookup_retry_options.clone();
+        let lookup_timeout = self.lookup_timeout;
+        let max_concurrent_lookups = self.max_concurrent_lookups;
+        let max_redirects = self.max_redirects;
+
+        let _ = Exe::spawn(Box::pin(async move {
+            loop {
+                Exe::delay(LOOKUP_CACHE_REFRESH_INTERVAL).await;
+
+                let (manager, lookup_cache) = match (manager.upgrade(), lookup_cache.upgrade()) {
+                    (Some(manager), Some(lookup_cache)) => (manager, lookup_cache),
+                    _ => break,
+                };
+                let discovery = ServiceDiscovery {
+                    manager,
+                    lookup_retry_options: lookup_retry_options.clone(),
+                    lookup_timeout,
+                    max_concurrent_lookups,
+                    lookup_cache,
+                    max_redirects,
+                    refresh_task_init: Arc::new(Once::new()),
+                };
+
+                discovery.lookup_cache.evict_expired();
+                for topic in discovery.lookup_cache.topics_nearing_expiry() {
+                    // bounded by lookup_timeout like every other lookup, so a
+                    // broker that never answers can't stall the refresh of
+                    // every other topic nearing expiry in this tick
+                    let result = discovery
+                        .with_lookup_deadline(discovery.lookup_topic_inner(topic.clone()))
+                        .await;
+                    match result {
+                        Ok(address) => discovery.lookup_cache.insert(topic, address),
+                        Err(e) => error!(
+                            "background refresh of cached lookup for {} failed: {:?}",
+                            topic, e
+                        ),
+                    }
+                }
+            }
+        }));
+    }
+
+    /// races `fut` against `self.lookup_timeout`, bounding the total time spent
+    /// in a lookup's retry/redirect loop rather than any single RPC
+    async fn with_lookup_deadline<T>(
+        &self,
+        fut: impl std::future::Future<Output = Result<T, ServiceDiscoveryError>>,
+    ) -> Result<T, ServiceDiscoveryError> {
+        let timeout = self.lookup_timeout;
+        let delay = Exe::delay(timeout);
+        pin_mut!(fut);
+        pin_mut!(delay);
+        match select(fut, delay).await {
+            Either::Left((res, _)) => res,
+            Either::Right((_, _)) => Err(ServiceDiscoveryError::Timeout(timeout)),
+        }
     }
 
     /// get the broker address for a topic
@@ -29,14 +354,36 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
         &self,
         topic: S,
     ) -> Result<BrokerAddress, ServiceDiscoveryError> {
+        self.ensure_cache_refresh_task();
         let topic = topic.into();
+        if let Some(address) = self.lookup_cache.get(&topic) {
+            return Ok(address);
+        }
+
+        let address = self
+            .with_lookup_deadline(self.lookup_topic_inner(topic.clone()))
+            .await?;
+        self.lookup_cache.insert(topic, address.clone());
+        Ok(address)
+    }
+
+    async fn lookup_topic_inner(
+        &self,
+        topic: String,
+    ) -> Result<BrokerAddress, ServiceDiscoveryError> {
         let mut proxied_query = false;
         let mut conn = self.manager.get_base_connection().await?;
         let base_url = self.manager.url.clone();
         let mut is_authoritative = false;
         let mut broker_address = self.manager.get_base_address();
+        let mut redirects = 0u32;
+        let mut redirect_path: Vec<String> = Vec::new();
+        // seed with the starting broker so a redirect chain that loops back
+        // to it (A -> B -> A) is caught on the first return to A, not the second
+        let mut visited_brokers: HashSet<String> =
+            HashSet::from([broker_address.broker_url.clone()]);
 
-        let mut max_retries = 20u8;
+        let mut retries = 0u32;
         loop {
             let response = match conn
                 .sender()
@@ -46,6 +393,7 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
                 Ok(res) => res,
                 Err(ConnectionError::Disconnected) => {
                     error!("tried to lookup a topic but connection was closed, reconnecting...");
+                    self.lookup_cache.invalidate(&topic);
                     conn = self.manager.get_connection(&broker_address).await?;
                     conn.sender()
                         .lookup_topic(topic.to_string(), is_authoritative)
@@ -60,11 +408,15 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
             {
                 let error = response.error.and_then(crate::error::server_error);
                 if error == Some(crate::message::proto::ServerError::ServiceNotReady)
-                    && max_retries > 0
+                    && retries < self.lookup_retry_options.max_retries
                 {
-                    error!("lookup({}) answered ServiceNotReady, retrying request after 500ms (max_retries = {})", topic, max_retries);
-                    max_retries -= 1;
-                    Exe::delay(Duration::from_millis(500)).await;
+                    let delay = lookup_retry_delay(&self.lookup_retry_options, retries);
+                    error!(
+                        "lookup({}) answered ServiceNotReady, retrying request after {:?} (retries = {}/{})",
+                        topic, delay, retries, self.lookup_retry_options.max_retries
+                    );
+                    retries += 1;
+                    Exe::delay(delay).await;
                     continue;
                 }
                 return Err(ServiceDiscoveryError::Query(
@@ -108,8 +460,21 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
             };
 
             // if the response indicated a redirect, do another query
-            // to the target broker
+            // to the target broker; the previously cached address (if any)
+            // is no longer authoritative
             if redirect {
+                redirects += 1;
+                if redirect_chain_exhausted(
+                    broker_address.broker_url.clone(),
+                    redirects,
+                    self.max_redirects,
+                    &mut redirect_path,
+                    &mut visited_brokers,
+                ) {
+                    return Err(ServiceDiscoveryError::TooManyRedirects(redirect_path));
+                }
+
+                self.lookup_cache.invalidate(&topic);
                 conn = self.manager.get_connection(&broker_address).await?;
                 proxied_query = broker_address.proxy;
                 continue;
@@ -129,11 +494,19 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
     pub async fn lookup_partitioned_topic_number<S: Into<String>>(
         &self,
         topic: S,
+    ) -> Result<u32, ServiceDiscoveryError> {
+        self.ensure_cache_refresh_task();
+        self.with_lookup_deadline(self.lookup_partitioned_topic_number_inner(topic.into()))
+            .await
+    }
+
+    async fn lookup_partitioned_topic_number_inner(
+        &self,
+        topic: String,
     ) -> Result<u32, ServiceDiscoveryError> {
         let mut connection = self.manager.get_base_connection().await?;
-        let topic = topic.into();
 
-        let mut max_retries = 20u8;
+        let mut retries = 0u32;
         let response = loop {
             let response = match connection.sender().lookup_partitioned_topic(&topic).await {
                 Ok(res) => res,
@@ -151,11 +524,15 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
             {
                 let error = response.error.and_then(crate::error::server_error);
                 if error == Some(crate::message::proto::ServerError::ServiceNotReady)
-                    && max_retries > 0
+                    && retries < self.lookup_retry_options.max_retries
                 {
-                    error!("lookup_partitioned_topic_number({}) answered ServiceNotReady, retrying request after 500ms (max_retries = {})", topic, max_retries);
-                    max_retries -= 1;
-                    Exe::delay(Duration::from_millis(500)).await;
+                    let delay = lookup_retry_delay(&self.lookup_retry_options, retries);
+                    error!(
+                        "lookup_partitioned_topic_number({}) answered ServiceNotReady, retrying request after {:?} (retries = {}/{})",
+                        topic, delay, retries, self.lookup_retry_options.max_retries
+                    );
+                    retries += 1;
+                    Exe::delay(delay).await;
                     continue;
                 }
                 return Err(ServiceDiscoveryError::Query(
@@ -181,20 +558,64 @@ impl<Exe: Executor> ServiceDiscovery<Exe> {
         &self,
         topic: S,
     ) -> Result<Vec<(String, BrokerAddress)>, ServiceDiscoveryError> {
-        let topic = topic.into();
-        let partitions = self.lookup_partitioned_topic_number(&topic).await?;
-        let topics = (0..partitions)
-            .map(|nb| {
-                let t = format!("{}-partition-{}", topic, nb);
-                self.lookup_topic(t.clone())
-                    .map(move |address_res| match address_res {
-                        Err(e) => Err(e),
-                        Ok(address) => Ok((t, address)),
-                    })
-            })
-            .collect::<Vec<_>>();
-        try_join_all(topics).await
+        self.ensure_cache_refresh_task();
+        self.with_lookup_deadline(self.lookup_partitioned_topic_inner(topic.into()))
+            .await
     }
+
+    async fn lookup_partitioned_topic_inner(
+        &self,
+        topic: String,
+    ) -> Result<Vec<(String, BrokerAddress)>, ServiceDiscoveryError> {
+        let partitions = self
+            .lookup_partitioned_topic_number_inner(topic.clone())
+            .await?;
+        let mut pending = (0..partitions).map(|nb| (nb, format!("{}-partition-{}", topic, nb)));
+        let max_concurrent = self.max_concurrent_lookups.max(1);
+
+        let mut in_flight = FuturesUnordered::new();
+        // indexed by partition number rather than completion order, since
+        // FuturesUnordered (unlike the try_join_all this replaced) completes
+        // futures in whatever order they finish
+        let mut results: Vec<Option<(String, BrokerAddress)>> =
+            Vec::with_capacity(partitions as usize);
+        results.resize_with(partitions as usize, || None);
+
+        for (index, t) in pending.by_ref().take(max_concurrent) {
+            in_flight.push(self.lookup_one_partition(index, t));
+        }
+
+        while let Some(res) = in_flight.next().await {
+            let (index, entry) = res?;
+            results[index as usize] = Some(entry);
+            if let Some((index, t)) = pending.next() {
+                in_flight.push(self.lookup_one_partition(index, t));
+            }
+        }
+
+        Ok(into_ordered_partition_results(results))
+    }
+
+    async fn lookup_one_partition(
+        &self,
+        index: u32,
+        topic: String,
+    ) -> Result<(u32, (String, BrokerAddress)), ServiceDiscoveryError> {
+        let address = self.lookup_topic(topic.clone()).await?;
+        Ok((index, (topic, address)))
+    }
+}
+
+/// unwraps the per-partition results of `lookup_partitioned_topic_inner`'s
+/// fan-out back into partition order; every slot is filled unless a lookup
+/// failed, in which case the `?` above already returned before this runs
+fn into_ordered_partition_results(
+    results: Vec<Option<(String, BrokerAddress)>>,
+) -> Vec<(String, BrokerAddress)> {
+    results
+        .into_iter()
+        .map(|entry| entry.expect("every partition index is filled when no lookup failed"))
+        .collect()
 }
 
 struct LookupResponse {
@@ -239,3 +660,192 @@ fn convert_lookup_response(
         authoritative,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    fn test_address(broker_url: &str) -> BrokerAddress {
+        BrokerAddress {
+            url: Url::parse("pulsar://localhost:6650").unwrap(),
+            broker_url: broker_url.to_string(),
+            proxy: false,
+        }
+    }
+
+    #[test]
+    fn lookup_retry_delay_is_bounded_and_grows() {
+        let options = LookupRetryOptions {
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            max_retries: 20,
+        };
+
+        // jitter is in [0, delay/2], so the first attempt never goes below min_backoff
+        // and never exceeds min_backoff * 1.5
+        let first = lookup_retry_delay(&options, 0);
+        assert!(first >= options.min_backoff);
+        assert!(first <= options.min_backoff + options.min_backoff / 2);
+
+        // truncates at max_backoff however far the exponent grows
+        let later = lookup_retry_delay(&options, 10);
+        assert!(later >= options.max_backoff);
+        assert!(later <= options.max_backoff + options.max_backoff / 2);
+    }
+
+    #[test]
+    fn lookup_cache_round_trips_and_invalidates() {
+        let cache = LookupCache::new(Duration::from_secs(60), Duration::from_secs(30));
+        assert!(cache.get("persistent://p/ns/topic").is_none());
+
+        cache.insert(
+            "persistent://p/ns/topic".to_string(),
+            test_address("b1:6650"),
+        );
+        assert_eq!(
+            cache.get("persistent://p/ns/topic").unwrap().broker_url,
+            "b1:6650"
+        );
+
+        cache.invalidate("persistent://p/ns/topic");
+        assert!(cache.get("persistent://p/ns/topic").is_none());
+    }
+
+    #[test]
+    fn lookup_cache_expires_entries_after_ttl() {
+        let cache = LookupCache::new(Duration::from_millis(10), Duration::from_secs(30));
+        cache.insert("topic".to_string(), test_address("b1:6650"));
+        sleep(Duration::from_millis(25));
+        assert!(cache.get("topic").is_none());
+    }
+
+    #[test]
+    fn lookup_cache_evict_expired_removes_stale_entries() {
+        let cache = LookupCache::new(Duration::from_millis(10), Duration::from_secs(30));
+        cache.insert("expired".to_string(), test_address("b1:6650"));
+        cache.insert("fresh".to_string(), test_address("b2:6650"));
+        sleep(Duration::from_millis(25));
+        cache.insert("fresh".to_string(), test_address("b2:6650"));
+
+        cache.evict_expired();
+
+        assert_eq!(cache.entries.read().unwrap().len(), 1);
+        assert!(cache.entries.read().unwrap().contains_key("fresh"));
+    }
+
+    #[test]
+    fn lookup_cache_set_ttl_mutates_in_place() {
+        // a clone of the Arc wrapping the cache must observe the updated TTL,
+        // the way the background refresh task and a `ServiceDiscovery` clone do
+        let cache = Arc::new(LookupCache::new(
+            Duration::from_secs(60),
+            Duration::from_secs(30),
+        ));
+        let shared = cache.clone();
+        cache.set_ttl(Duration::from_millis(10));
+
+        shared.insert("topic".to_string(), test_address("b1:6650"));
+        sleep(Duration::from_millis(25));
+        assert!(shared.get("topic").is_none());
+    }
+
+    #[test]
+    fn lookup_cache_reports_topics_nearing_expiry() {
+        let cache = LookupCache::new(Duration::from_millis(20), Duration::from_millis(15));
+        cache.insert("hot-topic".to_string(), test_address("b1:6650"));
+        sleep(Duration::from_millis(10));
+        assert_eq!(cache.topics_nearing_expiry(), vec!["hot-topic".to_string()]);
+    }
+
+    #[test]
+    fn redirect_chain_detects_a_b_a_cycle() {
+        let mut path = Vec::new();
+        let mut visited = HashSet::new();
+
+        assert!(!redirect_chain_exhausted(
+            "broker-a".to_string(),
+            1,
+            20,
+            &mut path,
+            &mut visited
+        ));
+        assert!(!redirect_chain_exhausted(
+            "broker-b".to_string(),
+            2,
+            20,
+            &mut path,
+            &mut visited
+        ));
+        assert!(redirect_chain_exhausted(
+            "broker-a".to_string(),
+            3,
+            20,
+            &mut path,
+            &mut visited
+        ));
+        assert_eq!(path, vec!["broker-a", "broker-b", "broker-a"]);
+    }
+
+    #[test]
+    fn redirect_chain_seeded_with_starting_broker_detects_cycle_on_first_return() {
+        // mirrors lookup_topic_inner seeding visited_brokers with the
+        // starting broker before the loop begins
+        let mut path = Vec::new();
+        let mut visited = HashSet::from(["broker-a".to_string()]);
+
+        assert!(!redirect_chain_exhausted(
+            "broker-b".to_string(),
+            1,
+            20,
+            &mut path,
+            &mut visited
+        ));
+        // without seeding, this would only be flagged on a *second* return to broker-a
+        assert!(redirect_chain_exhausted(
+            "broker-a".to_string(),
+            2,
+            20,
+            &mut path,
+            &mut visited
+        ));
+    }
+
+    #[test]
+    fn redirect_chain_stops_at_max_redirects() {
+        let mut path = Vec::new();
+        let mut visited = HashSet::new();
+
+        for hop in 1..=5u32 {
+            assert!(!redirect_chain_exhausted(
+                format!("broker-{}", hop),
+                hop,
+                5,
+                &mut path,
+                &mut visited
+            ));
+        }
+        assert!(redirect_chain_exhausted(
+            "broker-6".to_string(),
+            6,
+            5,
+            &mut path,
+            &mut visited
+        ));
+    }
+
+    #[test]
+    fn ordered_partition_results_preserve_partition_index() {
+        // simulates `FuturesUnordered` completing partition 2 before partition 0 or 1
+        let mut results: Vec<Option<(String, BrokerAddress)>> = vec![None, None, None];
+        results[2] = Some(("t-partition-2".to_string(), test_address("b3:6650")));
+        results[0] = Some(("t-partition-0".to_string(), test_address("b1:6650")));
+        results[1] = Some(("t-partition-1".to_string(), test_address("b2:6650")));
+
+        let ordered = into_ordered_partition_results(results);
+        assert_eq!(
+            ordered.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>(),
+            vec!["t-partition-0", "t-partition-1", "t-partition-2"]
+        );
+    }
+}